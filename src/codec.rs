@@ -0,0 +1,450 @@
+use std::io::{self, Read, Write};
+use std::mem;
+
+use bincode::config;
+use crc::{crc32, Hasher32};
+
+use crate::device_profile::{self, DeviceProfile};
+use crate::error::CustomError;
+use crate::format::{CfgHeader, DataChunkHeader, CTCE8HeaderPart1, CTCE8HeaderPart2, READ_CHUNK_SIZE};
+
+// zlib compressBound() 的等价实现：raw deflate 在最坏情况下（近乎随机、不可
+// 压缩的数据）相对输入的膨胀幅度，再加上 zlib 流的 2 字节头与 4 字节 Adler-32
+// 校验尾。按此预先分配压缩输出缓冲区，避免像固定常量那样在较大的不可压缩块
+// 上造成缓冲区不够、触发重新分配（最坏情况下甚至被下游当成“放得下”的假设）。
+pub(crate) fn compress_bound(n: usize) -> usize {
+    n + (n >> 12) + (n >> 14) + (n >> 25) + 13 + 6
+}
+
+// 将输入数据按 READ_CHUNK_SIZE 切分为若干独立的块，每块互不依赖，可并行压缩。
+// 用 `chunks()` 对整个输入做一趟线性扫描，而不是反复 `split_off` ——后者每次
+// 都要把剩余部分整体拷到新分配里，对大文件是 O(n²)。
+pub(crate) fn split_into_chunks(data: Vec<u8>) -> Vec<Vec<u8>> {
+    data.chunks(READ_CHUNK_SIZE).map(|chunk| chunk.to_vec()).collect()
+}
+
+// 用 `jobs` 个工作线程并行压缩所有块，每个线程拥有独立的 `Compress` 实例。
+// 返回的压缩结果和 `chunks` 一一对应、顺序不变，之后由调用方串行地折算
+// chunk_end_offset 与链式 CRC32，这两者都依赖块的先后顺序，不能并行计算。
+pub(crate) fn compress_chunks_parallel(
+    chunks: Vec<Vec<u8>>,
+    jobs: usize,
+) -> Result<Vec<Vec<u8>>, flate2::CompressError> {
+    use flate2::{Compress, Compression, FlushCompress};
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+
+    let jobs = jobs.max(1).min(chunks.len().max(1));
+
+    let chunks = Arc::new(chunks);
+    let next_index = Arc::new(Mutex::new(0usize));
+    let results: Arc<Mutex<Vec<Option<Vec<u8>>>>> =
+        Arc::new(Mutex::new((0..chunks.len()).map(|_| None).collect()));
+
+    let mut handles = Vec::with_capacity(jobs);
+    for _ in 0..jobs {
+        let chunks = Arc::clone(&chunks);
+        let next_index = Arc::clone(&next_index);
+        let results = Arc::clone(&results);
+
+        handles.push(thread::spawn(
+            move || -> Result<(), flate2::CompressError> {
+                let mut compressor = Compress::new(Compression::best(), true);
+
+                loop {
+                    let index = {
+                        let mut next_index = next_index.lock().unwrap();
+                        if *next_index >= chunks.len() {
+                            break;
+                        }
+                        let index = *next_index;
+                        *next_index += 1;
+                        index
+                    };
+
+                    let before_compressed_size = chunks[index].len();
+                    let mut compressed: Vec<u8> =
+                        Vec::with_capacity(compress_bound(before_compressed_size));
+                    compressor.compress_vec(&chunks[index], &mut compressed, FlushCompress::Finish)?;
+                    compressor.reset();
+
+                    // 接近随机的数据在 best() 档位下可能被压“胀”；退化为
+                    // none() 档位重新压缩一次，该档位仍然产生合法的 zlib
+                    // 流（以存储块实现），膨胀幅度被 compress_bound 严格限定，
+                    // 不会让容器里出现失控的块大小。
+                    if compressed.len() >= before_compressed_size {
+                        let mut stored: Vec<u8> =
+                            Vec::with_capacity(compress_bound(before_compressed_size));
+                        let mut store_compressor = Compress::new(Compression::none(), true);
+                        store_compressor.compress_vec(
+                            &chunks[index],
+                            &mut stored,
+                            FlushCompress::Finish,
+                        )?;
+                        if stored.len() < compressed.len() {
+                            compressed = stored;
+                        }
+                    }
+
+                    results.lock().unwrap()[index] = Some(compressed);
+                }
+
+                Ok(())
+            },
+        ));
+    }
+
+    for handle in handles {
+        handle
+            .join()
+            .expect("compression worker thread panicked")?;
+    }
+
+    let results = Arc::try_unwrap(results)
+        .unwrap_or_else(|_| unreachable!("all worker threads have joined"))
+        .into_inner()
+        .unwrap();
+    Ok(results.into_iter().map(|chunk| chunk.unwrap()).collect())
+}
+
+/// 以 flate2 `ZlibEncoder`/`ZlibDecoder` 为范本提供的流式封装：接受原始 XML
+/// 字节，在 `finish()` 时一次性把完整的 CTCE8 容器（文件头 + 数据块流）写入
+/// 底层 writer。因为所有块都在 `finish()` 前已经缓冲好，编码过程不需要像
+/// 基于文件的实现那样先写占位头再回头 `seek`。
+pub struct CfgEncoder<W: Write> {
+    inner: Option<W>,
+    device_model_string: String,
+    profile: &'static DeviceProfile,
+    jobs: usize,
+    buffer: Vec<u8>,
+}
+
+impl<W: Write> CfgEncoder<W> {
+    pub fn new(inner: W, device_model_string: &str) -> CfgEncoder<W> {
+        CfgEncoder::with_jobs(inner, device_model_string, 1)
+    }
+
+    pub fn with_jobs(inner: W, device_model_string: &str, jobs: usize) -> CfgEncoder<W> {
+        let profile = device_profile::detect_by_model(device_model_string)
+            .unwrap_or_else(device_profile::default_profile);
+        CfgEncoder::with_profile(inner, device_model_string, jobs, profile)
+    }
+
+    /// 与 [`CfgEncoder::with_jobs`] 相同，但不根据 `device_model_string` 自动匹配
+    /// profile，而是使用调用方显式指定的 `profile`（对应 CLI 的 `--profile`）。
+    pub fn with_profile(
+        inner: W,
+        device_model_string: &str,
+        jobs: usize,
+        profile: &'static DeviceProfile,
+    ) -> CfgEncoder<W> {
+        CfgEncoder {
+            inner: Some(inner),
+            device_model_string: device_model_string.to_owned(),
+            profile,
+            jobs,
+            buffer: Vec::new(),
+        }
+    }
+
+    pub fn finish(mut self) -> Result<W, CustomError> {
+        let mut inner = self
+            .inner
+            .take()
+            .expect("CfgEncoder::finish called more than once");
+
+        let buffer = mem::replace(&mut self.buffer, Vec::new());
+        let chunks = split_into_chunks(buffer);
+
+        let mut big_endian_config = config();
+        big_endian_config.big_endian();
+        let mut little_endian_config = config();
+        little_endian_config.little_endian();
+
+        let uncompressed_file_size: u32 = chunks.iter().map(|chunk| chunk.len() as u32).sum();
+
+        let device_model_name_length = self.device_model_string.len() as u32;
+        let header_placeholder_length = mem::size_of::<CTCE8HeaderPart1>()
+            + mem::size_of_val(&device_model_name_length) // special_file_size 与该字段同为 u32
+            + mem::size_of::<CTCE8HeaderPart2>()
+            + mem::size_of_val(&device_model_name_length)
+            + self.device_model_string.len()
+            + mem::size_of::<CfgHeader>();
+
+        let data_chunk_header_size = mem::size_of::<DataChunkHeader>() as u32;
+        let before_compressed_sizes: Vec<u32> =
+            chunks.iter().map(|chunk| chunk.len() as u32).collect();
+        let compressed_chunks = compress_chunks_parallel(chunks, self.jobs)?;
+
+        let mut output_data_size = 0u32;
+        let mut chunk_end_offset = mem::size_of::<CfgHeader>() as u32;
+        let mut compressed_chunk_overlaying_crc32 = 0u32;
+        let mut chunk_stream: Vec<u8> = Vec::new();
+
+        for (i, compressed) in compressed_chunks.iter().enumerate() {
+            let after_compressed_size = compressed.len() as u32;
+
+            // 是否为最后一块由其原始（未压缩）大小是否凑满 READ_CHUNK_SIZE 判定
+            if before_compressed_sizes[i] == READ_CHUNK_SIZE as u32 {
+                chunk_end_offset += data_chunk_header_size + after_compressed_size;
+            } else {
+                chunk_end_offset = 0; // 以偏移值 0 标记接下来的数据块为最后一块
+            }
+
+            let data_chunk_header = DataChunkHeader {
+                before_compressed_size: before_compressed_sizes[i],
+                after_compressed_size,
+                chunk_end_offset,
+            };
+
+            let encoded: Vec<u8> = big_endian_config.serialize(&data_chunk_header)?;
+            chunk_stream.write_all(&encoded)?;
+            chunk_stream.write_all(compressed)?;
+            output_data_size += data_chunk_header_size + after_compressed_size;
+
+            let mut crc32_digest =
+                crc32::Digest::new_with_initial(crc32::IEEE, compressed_chunk_overlaying_crc32);
+            crc32_digest.write(compressed);
+            compressed_chunk_overlaying_crc32 = crc32_digest.sum32();
+        }
+
+        inner.write_all(&big_endian_config.serialize(&self.profile.header_part1)?)?;
+
+        {
+            let special_file_size =
+                output_data_size + header_placeholder_length as u32 - self.profile.file_size_delta as u32;
+            // 目前已知机型都是小端，也就这个地方特别
+            if self.profile.special_file_size_little_endian {
+                inner.write_all(&little_endian_config.serialize(&special_file_size)?)?;
+            } else {
+                inner.write_all(&big_endian_config.serialize(&special_file_size)?)?;
+            }
+        }
+
+        inner.write_all(&big_endian_config.serialize(&self.profile.header_part2)?)?;
+
+        inner.write_all(&big_endian_config.serialize(&device_model_name_length)?)?;
+        inner.write_all(self.device_model_string.as_bytes())?;
+
+        {
+            let mut cfg_header = CfgHeader {
+                flag1: [0x01020304, 0],
+                uncompressed_file_size,
+                output_data_with_header_size: output_data_size
+                    + mem::size_of::<CfgHeader>() as u32,
+                compress_chunk_size: READ_CHUNK_SIZE as u32,
+                compressed_chunk_overlying_crc32: compressed_chunk_overlaying_crc32,
+                cfg_header_crc32: 0,
+                blank_bytes: [0u32; 8],
+            };
+            let encoded: Vec<u8> = big_endian_config.serialize(&cfg_header)?;
+
+            let mut crc32_digest = crc32::Digest::new_with_initial(crc32::IEEE, 0u32);
+            crc32_digest.write(&(encoded.as_slice()[..24]));
+            cfg_header.cfg_header_crc32 = crc32_digest.sum32();
+
+            let encoded: Vec<u8> = big_endian_config.serialize(&cfg_header)?;
+            inner.write_all(&encoded)?;
+        }
+
+        inner.write_all(&chunk_stream)?;
+
+        Ok(inner)
+    }
+}
+
+impl<W: Write> Write for CfgEncoder<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buffer.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// `CfgDecoder` 校验 CTCE8 容器的各级文件头，并通过 `Read` 吐出解压后的 XML。
+/// 与 `CfgEncoder` 对称，按需解压、不要求底层 reader 支持 `Seek`。
+pub struct CfgDecoder<R: Read> {
+    inner: R,
+    cfg_header: CfgHeader,
+    last_chunk_end_offset: u32,
+    compressed_chunk_overlaying_crc32: u32,
+    pending: Vec<u8>,
+    finished: bool,
+}
+
+impl<R: Read> CfgDecoder<R> {
+    /// 构造解码器，完成全部文件头校验。profile 未指定时按
+    /// [`device_profile::resolve`] 自动选定；已知 profile 时用
+    /// [`CfgDecoder::with_profile`] 跳过自动匹配。
+    pub fn new(inner: R) -> Result<CfgDecoder<R>, CustomError> {
+        CfgDecoder::with_input_size_and_profile(inner, None, None)
+    }
+
+    /// 与 [`CfgDecoder::new`] 相同，但额外用 `input_file_size`（如果已知）校验
+    /// `input_file_size - special_file_size == profile.file_size_delta` 这一在
+    /// ZXHN F450 上观察到的关系（该值为 128），其他地区机型未知。
+    pub fn with_input_size(
+        inner: R,
+        input_file_size: Option<u64>,
+    ) -> Result<CfgDecoder<R>, CustomError> {
+        CfgDecoder::with_input_size_and_profile(inner, input_file_size, None)
+    }
+
+    /// 跳过 profile 自动匹配，直接使用调用方指定的 profile（对应 CLI 的
+    /// `--profile`）校验文件头。
+    pub fn with_profile(
+        inner: R,
+        input_file_size: Option<u64>,
+        profile: &'static DeviceProfile,
+    ) -> Result<CfgDecoder<R>, CustomError> {
+        CfgDecoder::with_input_size_and_profile(inner, input_file_size, Some(profile))
+    }
+
+    fn with_input_size_and_profile(
+        mut inner: R,
+        input_file_size: Option<u64>,
+        profile: Option<&'static DeviceProfile>,
+    ) -> Result<CfgDecoder<R>, CustomError> {
+        use crate::error::{turn, UnpackError};
+        use crate::header;
+
+        let parsed = header::parse_and_validate(&mut inner, input_file_size, profile)?;
+
+        if let Some((expected, computed)) = parsed.cfg_header_crc32_mismatch {
+            turn!(UnpackError::CfgHeaderCrcMismatch {
+                offset: parsed.cfg_header_offset + 24,
+                expected,
+                computed,
+            })
+        }
+
+        Ok(CfgDecoder {
+            inner,
+            cfg_header: parsed.cfg_header,
+            last_chunk_end_offset: parsed.data_area_offset,
+            compressed_chunk_overlaying_crc32: 0,
+            pending: Vec::new(),
+            finished: false,
+        })
+    }
+
+    fn decode_next_chunk(&mut self) -> Result<(), CustomError> {
+        use crate::error::{turn, UnpackError};
+        use flate2::{Decompress, FlushDecompress};
+
+        let data_chunk_header_size = mem::size_of::<DataChunkHeader>() as u32;
+        let chunk_byte_offset = self.last_chunk_end_offset;
+
+        let mut big_endian_config = config();
+        big_endian_config.big_endian();
+
+        let data_chunk_header: DataChunkHeader;
+        {
+            let mut read_buffer: Vec<u8> = vec![0u8; data_chunk_header_size as usize];
+            self.inner.read_exact(&mut read_buffer)?;
+            data_chunk_header = big_endian_config.deserialize(&read_buffer)?;
+        }
+
+        let mut read_buffer: Vec<u8>;
+        if data_chunk_header.chunk_end_offset != 0 {
+            read_buffer = vec![
+                0u8;
+                (data_chunk_header.chunk_end_offset
+                    - data_chunk_header_size
+                    - self.last_chunk_end_offset) as usize
+            ];
+            self.inner.read_exact(&mut read_buffer)?;
+            self.last_chunk_end_offset = data_chunk_header.chunk_end_offset;
+        } else {
+            read_buffer = Vec::with_capacity(data_chunk_header.after_compressed_size as usize);
+            self.inner.read_to_end(&mut read_buffer)?;
+        }
+
+        let mut decompressor = Decompress::new(true);
+        let mut write_buffer: Vec<u8> =
+            Vec::with_capacity(data_chunk_header.before_compressed_size as usize);
+        decompressor.decompress_vec(&read_buffer, &mut write_buffer, FlushDecompress::Finish)?;
+        self.pending.extend_from_slice(&write_buffer);
+
+        let mut crc32_digest = crc32::Digest::new_with_initial(
+            crc32::IEEE,
+            self.compressed_chunk_overlaying_crc32,
+        );
+        crc32_digest.write(&read_buffer);
+        self.compressed_chunk_overlaying_crc32 = crc32_digest.sum32();
+
+        if data_chunk_header.chunk_end_offset == 0 {
+            self.finished = true;
+
+            if self.compressed_chunk_overlaying_crc32
+                != self.cfg_header.compressed_chunk_overlying_crc32
+            {
+                turn!(UnpackError::ChunkCrcMismatch {
+                    offset: chunk_byte_offset as u64,
+                    expected: self.cfg_header.compressed_chunk_overlying_crc32,
+                    computed: self.compressed_chunk_overlaying_crc32,
+                })
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl<R: Read> Read for CfgDecoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.pending.is_empty() && !self.finished {
+            self.decode_next_chunk()
+                .map_err(|err| io::Error::new(io::ErrorKind::Other, format!("{:?}", err)))?;
+        }
+
+        let n = buf.len().min(self.pending.len());
+        buf[..n].copy_from_slice(&self.pending[..n]);
+        self.pending.drain(..n);
+        Ok(n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 跨越多个 READ_CHUNK_SIZE 边界的数据（按 256 字节循环的字节序列），确保
+    // 并行压缩路径会真正跑满多个工作线程，而不是退化成单块。
+    fn sample_xml(n: usize) -> Vec<u8> {
+        (0..n).map(|i| (i % 256) as u8).collect()
+    }
+
+    #[test]
+    fn pack_output_is_byte_identical_regardless_of_job_count() {
+        let xml = sample_xml(READ_CHUNK_SIZE * 3 + 1234);
+
+        let mut single_threaded = CfgEncoder::with_jobs(Vec::new(), "ZXHN F450V2", 1);
+        single_threaded.write_all(&xml).unwrap();
+        let single_threaded_output = single_threaded.finish().unwrap();
+
+        let mut multi_threaded = CfgEncoder::with_jobs(Vec::new(), "ZXHN F450V2", 4);
+        multi_threaded.write_all(&xml).unwrap();
+        let multi_threaded_output = multi_threaded.finish().unwrap();
+
+        assert_eq!(single_threaded_output, multi_threaded_output);
+    }
+
+    #[test]
+    fn decoder_round_trips_encoder_output() {
+        let xml = sample_xml(READ_CHUNK_SIZE * 3 + 1234);
+
+        let mut encoder = CfgEncoder::with_jobs(Vec::new(), "ZXHN F450V2", 4);
+        encoder.write_all(&xml).unwrap();
+        let packed = encoder.finish().unwrap();
+
+        let mut decoder = CfgDecoder::new(io::Cursor::new(packed)).unwrap();
+        let mut decoded = Vec::new();
+        decoder.read_to_end(&mut decoded).unwrap();
+
+        assert_eq!(decoded, xml);
+    }
+}
@@ -0,0 +1,77 @@
+// 不同地区/机型的 ZTE 路由器在 CTCE8 容器格式上观察到的差异，目前已知仅
+// ZXHN F450 一款。把这些差异抽出为 `DeviceProfile`，可以在不重新编译的情况下
+// 通过 `--profile` 指定，或者根据嵌入的机型字符串自动匹配，为以后支持其他
+// 机型留出扩展点。
+
+use crate::format::{CTCE8HeaderPart1, CTCE8HeaderPart2, CTCE8_HEADER_PART1, CTCE8_HEADER_PART2};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DeviceProfile {
+    /// 传给 `--profile` 时使用的名字
+    pub name: &'static str,
+    /// 用于从嵌入的机型字符串自动匹配 profile 的前缀
+    pub model_prefix: &'static str,
+    pub(crate) header_part1: CTCE8HeaderPart1,
+    pub(crate) header_part2: CTCE8HeaderPart2,
+    /// `input_file_size - special_file_size` 应有的差值，在 ZXHN F450 上是 128
+    pub(crate) file_size_delta: u64,
+    /// special_file_size 字段是否以小端写入（目前已知机型都是小端，这是个特例）
+    pub(crate) special_file_size_little_endian: bool,
+}
+
+pub static ZXHN_F450: DeviceProfile = DeviceProfile {
+    name: "zxhn-f450",
+    model_prefix: "ZXHN F450",
+    header_part1: CTCE8_HEADER_PART1,
+    header_part2: CTCE8_HEADER_PART2,
+    file_size_delta: 128,
+    special_file_size_little_endian: true,
+};
+
+/// 目前已知的全部 profile，其他地区机型未知，待补充
+pub static KNOWN_PROFILES: &[&DeviceProfile] = &[&ZXHN_F450];
+
+pub fn default_profile() -> &'static DeviceProfile {
+    &ZXHN_F450
+}
+
+pub fn by_name(name: &str) -> Option<&'static DeviceProfile> {
+    KNOWN_PROFILES
+        .iter()
+        .copied()
+        .find(|profile| profile.name.eq_ignore_ascii_case(name))
+}
+
+/// 根据机型字符串自动匹配 profile
+pub fn detect_by_model(device_model_string: &str) -> Option<&'static DeviceProfile> {
+    KNOWN_PROFILES
+        .iter()
+        .copied()
+        .find(|profile| device_model_string.starts_with(profile.model_prefix))
+}
+
+/// 按魔数精确匹配 profile；解包时选 profile 应该用 [`resolve`]。
+///
+/// 参数类型是 `pub(crate)`，因此这个函数本身也只能是 `pub(crate)`。
+pub(crate) fn detect_by_header(
+    header_part1: &CTCE8HeaderPart1,
+    header_part2: &CTCE8HeaderPart2,
+) -> Vec<&'static DeviceProfile> {
+    KNOWN_PROFILES
+        .iter()
+        .copied()
+        .filter(|profile| &profile.header_part1 == header_part1 && &profile.header_part2 == header_part2)
+        .collect()
+}
+
+/// 解包时在未显式指定 `--profile` 时选用哪个 profile。优先按机型字符串前缀
+/// 匹配，这样即使文件头魔数已损坏也能选对 profile、让逐字段校验报告出实际
+/// 损坏的偏移；匹配不到机型字符串时才退回按 [`detect_by_header`] 的魔数匹配。
+pub(crate) fn resolve(
+    device_model_string: &str,
+    header_part1: &CTCE8HeaderPart1,
+    header_part2: &CTCE8HeaderPart2,
+) -> Option<&'static DeviceProfile> {
+    detect_by_model(device_model_string)
+        .or_else(|| detect_by_header(header_part1, header_part2).into_iter().next())
+}
@@ -0,0 +1,148 @@
+use std::error::{self, Error};
+use std::fmt;
+use std::io;
+
+// 错误统一处理
+macro_rules! turn {
+    ($expr:expr) => {
+        return core::result::Result::Err(core::convert::From::from($expr));
+    };
+}
+pub(crate) use turn;
+
+#[derive(Debug)]
+pub enum CustomError {
+    IoError(io::Error),
+    UnpackError(UnpackError),
+    DeserializeOrSerializeError(Box<bincode::ErrorKind>),
+    DecompressError(flate2::DecompressError),
+    CompressError(flate2::CompressError),
+}
+
+impl From<io::Error> for CustomError {
+    fn from(err: io::Error) -> CustomError {
+        CustomError::IoError(err)
+    }
+}
+
+impl From<UnpackError> for CustomError {
+    fn from(err: UnpackError) -> CustomError {
+        CustomError::UnpackError(err)
+    }
+}
+
+impl From<Box<bincode::ErrorKind>> for CustomError {
+    fn from(err: Box<bincode::ErrorKind>) -> CustomError {
+        CustomError::DeserializeOrSerializeError(err)
+    }
+}
+
+impl From<flate2::DecompressError> for CustomError {
+    fn from(err: flate2::DecompressError) -> CustomError {
+        CustomError::DecompressError(err)
+    }
+}
+
+impl From<flate2::CompressError> for CustomError {
+    fn from(err: flate2::CompressError) -> CustomError {
+        CustomError::CompressError(err)
+    }
+}
+
+/// 标识出错的是容器的哪一级文件头，供 [`UnpackError::BadHeaderMagic`] 使用。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HeaderPart {
+    Ctce8HeaderPart1,
+    Ctce8HeaderPart2,
+    CfgHeader,
+}
+
+impl fmt::Display for HeaderPart {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            HeaderPart::Ctce8HeaderPart1 => write!(f, "CTCE8HeaderPart1"),
+            HeaderPart::Ctce8HeaderPart2 => write!(f, "CTCE8HeaderPart2"),
+            HeaderPart::CfgHeader => write!(f, "CfgHeader"),
+        }
+    }
+}
+
+/// 结构化、自带偏移量的解包错误，取代原先单一的 `reason: &str`。每个变体都
+/// 携带校验失败处在文件中的字节偏移：对于数据区之前的文件头（`offset`
+/// 从文件起始算起），对于数据区内部的校验（`offset` 从 cfg header 起始算
+/// 起，与容器自身 `chunk_end_offset` 字段的寻址方式一致）。
+#[derive(Debug)]
+pub enum UnpackError {
+    BadHeaderMagic {
+        which: HeaderPart,
+        offset: u64,
+    },
+    UnknownDeviceProfile {
+        offset: u64,
+    },
+    FileSizeMismatch {
+        offset: u64,
+        expected: u64,
+        actual: u64,
+    },
+    ChunkCrcMismatch {
+        offset: u64,
+        expected: u32,
+        computed: u32,
+    },
+    CfgHeaderCrcMismatch {
+        offset: u64,
+        expected: u32,
+        computed: u32,
+    },
+}
+
+impl fmt::Display for UnpackError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            UnpackError::BadHeaderMagic { which, offset } => write!(
+                f,
+                "文件格式不正确（{} magic 校验失败，位于偏移 {:#x}）",
+                which, offset
+            ),
+            UnpackError::UnknownDeviceProfile { offset } => write!(
+                f,
+                "文件格式不正确，没有与任何已知机型 profile 匹配（位于偏移 {:#x}）",
+                offset
+            ),
+            UnpackError::FileSizeMismatch {
+                offset,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "文件大小不正确，可能不兼容该版本 cfg 文件（偏移 {:#x} 处，期望 {}，实际 {}）",
+                offset, expected, actual
+            ),
+            UnpackError::ChunkCrcMismatch {
+                offset,
+                expected,
+                computed,
+            } => write!(
+                f,
+                "文件数据不正确，压缩数据 CRC32 校验失败（偏移 {:#x} 处，期望 {:#010x}，计算得到 {:#010x}）",
+                offset, expected, computed
+            ),
+            UnpackError::CfgHeaderCrcMismatch {
+                offset,
+                expected,
+                computed,
+            } => write!(
+                f,
+                "文件格式不正确，cfg header crc32 校验失败（偏移 {:#x} 处，期望 {:#010x}，计算得到 {:#010x}）",
+                offset, expected, computed
+            ),
+        }
+    }
+}
+
+impl error::Error for UnpackError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        None
+    }
+}
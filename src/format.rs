@@ -0,0 +1,53 @@
+// ctce8 的一些文件数据格式定义
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone, Copy)]
+pub(crate) struct CTCE8HeaderPart1 {
+    pub(crate) flag1: [u32; 4],
+    pub(crate) blank_bytes1: [u32; 2],
+    pub(crate) flag2: u32,
+    pub(crate) blank_bytes2: [u32; 8],
+    pub(crate) flag3: u32,
+    pub(crate) flag4: [u32; 2],
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone, Copy)]
+pub(crate) struct CTCE8HeaderPart2 {
+    pub(crate) blank_bytes3: [u32; 13],
+    pub(crate) flag5: [u32; 2],
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub(crate) struct CfgHeader {
+    pub(crate) flag1: [u32; 2],
+    pub(crate) uncompressed_file_size: u32,
+    pub(crate) output_data_with_header_size: u32,
+    pub(crate) compress_chunk_size: u32,
+    pub(crate) compressed_chunk_overlying_crc32: u32,
+    pub(crate) cfg_header_crc32: u32,
+    pub(crate) blank_bytes: [u32; 8],
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub(crate) struct DataChunkHeader {
+    pub(crate) before_compressed_size: u32,
+    pub(crate) after_compressed_size: u32,
+    pub(crate) chunk_end_offset: u32,
+}
+
+pub(crate) const READ_CHUNK_SIZE: usize = 0x10000;
+
+pub(crate) static CTCE8_HEADER_PART1: CTCE8HeaderPart1 = CTCE8HeaderPart1 {
+    flag1: [0x99999999, 0x44444444, 0x55555555, 0xAAAAAAAA],
+    blank_bytes1: [0; 2],
+    flag2: 0x04000000,
+    blank_bytes2: [0; 8],
+    flag3: 0x40000000,
+    flag4: [0x02000000, 0x80000000],
+};
+
+pub(crate) static CTCE8_HEADER_PART2: CTCE8HeaderPart2 = CTCE8HeaderPart2 {
+    blank_bytes3: [0; 13],
+    flag5: [0x04030201, 0],
+};
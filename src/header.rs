@@ -0,0 +1,161 @@
+// CTCE8 容器文件头（profile 选择 + 逐级校验）的解析逻辑，供 `CfgDecoder`
+// 与 `verify_reader` 共用，避免两处各自维护一份几乎相同又容易各自出错的拷贝。
+
+use std::io::Read;
+use std::mem;
+
+use bincode::config;
+use crc::{crc32, Hasher32};
+
+use crate::device_profile::{self, DeviceProfile};
+use crate::error::{turn, HeaderPart, UnpackError};
+use crate::format::{CfgHeader, CTCE8HeaderPart1, CTCE8HeaderPart2};
+use crate::CustomError;
+
+/// 文件头解析、profile 选定与文件头级校验全部通过后的结果。
+pub(crate) struct ParsedHeader {
+    pub(crate) profile: &'static DeviceProfile,
+    pub(crate) cfg_header: CfgHeader,
+    /// cfg header 自身校验用的 crc32，与声明值不符时不中止，交由调用方决定
+    /// 如何处理（`CfgDecoder` 直接报错，`verify_reader` 记入报告）。
+    pub(crate) cfg_header_crc32_mismatch: Option<(u32, u32)>, // (expected, computed)
+    /// cfg header 起始处的字节偏移（从文件起始算起）
+    pub(crate) cfg_header_offset: u64,
+    /// cfg header 结束处（数据区第一个块起）的字节偏移，与容器自身
+    /// `chunk_end_offset` 字段的寻址方式一致。
+    pub(crate) data_area_offset: u32,
+}
+
+/// 读取并校验 CTCE8HeaderPart1/2、机型字符串与 CfgHeader，直到（但不包括）
+/// 数据区。`profile` 为 `None` 时自动选定，见 [`device_profile::resolve`]。
+pub(crate) fn parse_and_validate<R: Read>(
+    inner: &mut R,
+    input_file_size: Option<u64>,
+    profile: Option<&'static DeviceProfile>,
+) -> Result<ParsedHeader, CustomError> {
+    let mut big_endian_config = config();
+    big_endian_config.big_endian();
+    let mut little_endian_config = config();
+    little_endian_config.little_endian();
+
+    let mut offset = 0u64;
+
+    let header_part1: CTCE8HeaderPart1;
+    let header_part1_offset = offset;
+    {
+        let size = mem::size_of::<CTCE8HeaderPart1>();
+        let mut read_buffer: Vec<u8> = vec![0u8; size];
+        inner.read_exact(&mut read_buffer)?;
+        header_part1 = big_endian_config.deserialize(&read_buffer)?;
+        offset += size as u64;
+    }
+
+    let special_file_size_bytes: Vec<u8>;
+    let special_file_size_offset = offset;
+    {
+        let mut read_buffer: Vec<u8> = vec![0u8; mem::size_of::<u32>()];
+        inner.read_exact(&mut read_buffer)?;
+        special_file_size_bytes = read_buffer;
+        offset += mem::size_of::<u32>() as u64;
+    }
+
+    let header_part2: CTCE8HeaderPart2;
+    let header_part2_offset = offset;
+    {
+        let size = mem::size_of::<CTCE8HeaderPart2>();
+        let mut read_buffer: Vec<u8> = vec![0u8; size];
+        inner.read_exact(&mut read_buffer)?;
+        header_part2 = big_endian_config.deserialize(&read_buffer)?;
+        offset += size as u64;
+    }
+
+    let device_model_string: String;
+    let device_model_string_offset;
+    {
+        let device_model_string_length: u32;
+        let mut read_buffer: Vec<u8> = vec![0u8; mem::size_of::<u32>()];
+        inner.read_exact(&mut read_buffer)?;
+        device_model_string_length = big_endian_config.deserialize(&read_buffer)?;
+        offset += mem::size_of::<u32>() as u64;
+        device_model_string_offset = offset;
+
+        let mut device_model_string_bytes = vec![0u8; device_model_string_length as usize];
+        inner.read_exact(&mut device_model_string_bytes)?;
+        device_model_string = String::from_utf8_lossy(&device_model_string_bytes).into_owned();
+        offset += device_model_string_length as u64;
+    }
+
+    // profile 选定策略见 device_profile::resolve 的文档注释。
+    let profile = match profile {
+        Some(profile) => profile,
+        None => device_profile::resolve(&device_model_string, &header_part1, &header_part2)
+            .ok_or(UnpackError::UnknownDeviceProfile {
+                offset: device_model_string_offset,
+            })?,
+    };
+
+    if header_part1 != profile.header_part1 {
+        turn!(UnpackError::BadHeaderMagic {
+            which: HeaderPart::Ctce8HeaderPart1,
+            offset: header_part1_offset,
+        })
+    }
+    if header_part2 != profile.header_part2 {
+        turn!(UnpackError::BadHeaderMagic {
+            which: HeaderPart::Ctce8HeaderPart2,
+            offset: header_part2_offset,
+        })
+    }
+
+    // special_file_size 的字节序由 profile 决定，因此在 profile 选定后才解析
+    let special_file_size: u32 = if profile.special_file_size_little_endian {
+        little_endian_config.deserialize(&special_file_size_bytes)?
+    } else {
+        big_endian_config.deserialize(&special_file_size_bytes)?
+    };
+
+    if let Some(input_file_size) = input_file_size {
+        if input_file_size - special_file_size as u64 != profile.file_size_delta {
+            turn!(UnpackError::FileSizeMismatch {
+                offset: special_file_size_offset,
+                expected: profile.file_size_delta,
+                actual: input_file_size - special_file_size as u64,
+            })
+        }
+    }
+
+    let cfg_header_offset = offset;
+    let cfg_header: CfgHeader;
+    let cfg_header_crc32_mismatch;
+    {
+        let mut read_buffer: Vec<u8> = vec![0u8; mem::size_of::<CfgHeader>()];
+        inner.read_exact(&mut read_buffer)?;
+        cfg_header = big_endian_config.deserialize(&read_buffer)?;
+
+        if cfg_header.flag1 != [0x01020304u32, 0u32] || cfg_header.blank_bytes != [0u32; 8] {
+            turn!(UnpackError::BadHeaderMagic {
+                which: HeaderPart::CfgHeader,
+                offset: cfg_header_offset,
+            })
+        }
+
+        let mut crc32_digest = crc32::Digest::new_with_initial(crc32::IEEE, 0u32);
+        crc32_digest.write(&(read_buffer.as_slice()[..24]));
+        let computed_cfg_header_crc32 = crc32_digest.sum32();
+        cfg_header_crc32_mismatch = if cfg_header.cfg_header_crc32 != computed_cfg_header_crc32 {
+            Some((cfg_header.cfg_header_crc32, computed_cfg_header_crc32))
+        } else {
+            None
+        };
+    }
+
+    let data_area_offset = mem::size_of::<CfgHeader>() as u32;
+
+    Ok(ParsedHeader {
+        profile,
+        cfg_header,
+        cfg_header_crc32_mismatch,
+        cfg_header_offset,
+        data_area_offset,
+    })
+}
@@ -0,0 +1,84 @@
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter, Write};
+
+mod codec;
+pub mod device_profile;
+mod error;
+mod format;
+mod header;
+mod verify;
+
+pub use codec::{CfgDecoder, CfgEncoder};
+pub use device_profile::DeviceProfile;
+pub use error::{CustomError, HeaderPart, UnpackError};
+pub use verify::{ChunkMismatch, VerifyReport};
+
+/// 将 `xml_file_path` 处的 XML 文件打包为 CTCE8 CFG 文件，写到 `ctce8_file_path`。
+/// `jobs` 控制压缩各数据块时使用的工作线程数；`profile` 为 `None` 时按
+/// `device_model_string` 自动匹配，详见 [`CfgEncoder`] 与 [`device_profile`]。
+pub fn pack_to_cfg(
+    xml_file_path: &str,
+    ctce8_file_path: &str,
+    device_model_string: &str,
+    jobs: usize,
+    profile: Option<&'static DeviceProfile>,
+) -> Result<(), CustomError> {
+    let xml_bytes = fs::read(xml_file_path)?;
+
+    let output_file = File::create(ctce8_file_path)?;
+    let output_stream = BufWriter::new(output_file);
+
+    let profile = profile
+        .or_else(|| device_profile::detect_by_model(device_model_string))
+        .unwrap_or_else(device_profile::default_profile);
+
+    let mut encoder = CfgEncoder::with_profile(output_stream, device_model_string, jobs, profile);
+    encoder.write_all(&xml_bytes)?;
+    encoder.finish()?;
+
+    Ok(())
+}
+
+/// 将 `ctce8_file_path` 处的 CTCE8 CFG 文件解包为 `xml_file_path` 处的 XML 文件。
+/// `profile` 为 `None` 时按文件头与嵌入的机型字符串自动匹配，详见 [`CfgDecoder`]。
+pub fn unpack_to_xml(
+    ctce8_file_path: &str,
+    xml_file_path: &str,
+    profile: Option<&'static DeviceProfile>,
+) -> Result<(), CustomError> {
+    let input_file_size = fs::metadata(ctce8_file_path)?.len();
+
+    let input_file = File::open(ctce8_file_path)?;
+    let input_stream = BufReader::new(input_file);
+
+    let output_file = File::create(xml_file_path)?;
+    let mut output_stream = BufWriter::new(output_file);
+
+    let mut decoder = match profile {
+        Some(profile) => CfgDecoder::with_profile(input_stream, Some(input_file_size), profile)?,
+        None => CfgDecoder::with_input_size(input_stream, Some(input_file_size))?,
+    };
+    std::io::copy(&mut decoder, &mut output_stream)?;
+
+    Ok(())
+}
+
+/// 对 `ctce8_file_path` 处的 CFG 文件做只读的完整性校验，不写出 XML 文件。
+/// 与 `unpack_to_xml` 不同，数据区中的校验问题不会在第一个错误处中止，而是
+/// 汇总进返回的 [`VerifyReport`]（哪些问题仍直接返回 `Err` 见其文档），适合在
+/// 刷机前批量核查备份文件。
+pub fn verify_cfg(
+    ctce8_file_path: &str,
+    profile: Option<&'static DeviceProfile>,
+) -> Result<VerifyReport, CustomError> {
+    let input_file_size = fs::metadata(ctce8_file_path)?.len();
+
+    let input_file = File::open(ctce8_file_path)?;
+    let input_stream = BufReader::new(input_file);
+
+    Ok(verify::verify_reader(
+        input_stream,
+        Some(input_file_size),
+        profile,
+    )?)
+}
@@ -0,0 +1,151 @@
+// `verify` 子命令：对一个 CFG 文件做只读的完整性校验，不写出任何 XML。
+// 与 `unpack`/`CfgDecoder` 不同，这里发现问题后不会在第一个错误处中止，而是
+// 走完整个数据区、收集所有校验问题后一并报告（数据区读不下去时除外，见
+// `VerifyReport`），便于在刷机前批量核查备份文件。
+
+use std::io::Read;
+use std::mem;
+
+use bincode::config;
+use crc::{crc32, Hasher32};
+
+use crate::device_profile::DeviceProfile;
+use crate::error::CustomError;
+use crate::format::DataChunkHeader;
+use crate::header;
+
+/// 单个数据块在解压校验中发现的问题，带上它在文件中的字节偏移，方便定位。
+#[derive(Debug)]
+pub struct ChunkMismatch {
+    pub chunk_index: usize,
+    pub byte_offset: u32,
+    pub reason: String,
+}
+
+/// `verify` 的结果。文件头本身不是一个合法的 CTCE8 容器时会直接返回
+/// `Err(CustomError)`；进入数据区后，数据块解压失败、链式 CRC32 与解压总大小
+/// 不符都会汇总在这里，而不是在第一个问题处中止。数据区本身读不下去时（文件
+/// 被截断、数据块头无法解析）仍直接返回 `Err(CustomError)`。
+#[derive(Debug, Default)]
+pub struct VerifyReport {
+    pub chunk_count: usize,
+    pub chunk_mismatches: Vec<ChunkMismatch>,
+    pub cfg_header_crc32_mismatch: Option<(u32, u32)>, // (expected, computed)
+    pub chained_crc32_mismatch: Option<(u32, u32)>,    // (expected, computed)
+    pub uncompressed_size_mismatch: Option<(u32, u32)>, // (expected, computed)
+}
+
+impl VerifyReport {
+    pub fn is_ok(&self) -> bool {
+        self.chunk_mismatches.is_empty()
+            && self.cfg_header_crc32_mismatch.is_none()
+            && self.chained_crc32_mismatch.is_none()
+            && self.uncompressed_size_mismatch.is_none()
+    }
+}
+
+pub fn verify_reader<R: Read>(
+    mut inner: R,
+    input_file_size: Option<u64>,
+    profile: Option<&'static DeviceProfile>,
+) -> Result<VerifyReport, CustomError> {
+    use flate2::{Decompress, FlushDecompress};
+
+    let mut big_endian_config = config();
+    big_endian_config.big_endian();
+
+    let parsed = header::parse_and_validate(&mut inner, input_file_size, profile)?;
+    let cfg_header = parsed.cfg_header;
+
+    let mut report = VerifyReport::default();
+    report.cfg_header_crc32_mismatch = parsed.cfg_header_crc32_mismatch;
+
+    let data_chunk_header_size = mem::size_of::<DataChunkHeader>() as u32;
+    let mut last_chunk_end_offset = parsed.data_area_offset;
+    let mut compressed_chunk_overlaying_crc32 = 0u32;
+    let mut decompressed_total = 0u32;
+
+    loop {
+        let chunk_byte_offset = last_chunk_end_offset;
+
+        let data_chunk_header: DataChunkHeader;
+        {
+            let mut read_buffer: Vec<u8> = vec![0u8; data_chunk_header_size as usize];
+            inner.read_exact(&mut read_buffer)?;
+            data_chunk_header = big_endian_config.deserialize(&read_buffer)?;
+        }
+
+        let mut read_buffer: Vec<u8>;
+        let is_last_chunk = data_chunk_header.chunk_end_offset == 0;
+        if !is_last_chunk {
+            read_buffer = vec![
+                0u8;
+                (data_chunk_header.chunk_end_offset
+                    - data_chunk_header_size
+                    - last_chunk_end_offset) as usize
+            ];
+            inner.read_exact(&mut read_buffer)?;
+            last_chunk_end_offset = data_chunk_header.chunk_end_offset;
+        } else {
+            read_buffer = Vec::with_capacity(data_chunk_header.after_compressed_size as usize);
+            inner.read_to_end(&mut read_buffer)?;
+        }
+
+        let mut crc32_digest =
+            crc32::Digest::new_with_initial(crc32::IEEE, compressed_chunk_overlaying_crc32);
+        crc32_digest.write(&read_buffer);
+        compressed_chunk_overlaying_crc32 = crc32_digest.sum32();
+
+        let mut decompressor = Decompress::new(true);
+        let mut write_buffer: Vec<u8> =
+            Vec::with_capacity(data_chunk_header.before_compressed_size as usize);
+        match decompressor.decompress_vec(&read_buffer, &mut write_buffer, FlushDecompress::Finish) {
+            Ok(_) => decompressed_total += write_buffer.len() as u32,
+            Err(err) => report.chunk_mismatches.push(ChunkMismatch {
+                chunk_index: report.chunk_count,
+                byte_offset: chunk_byte_offset,
+                reason: format!("{}", err),
+            }),
+        }
+
+        report.chunk_count += 1;
+
+        if is_last_chunk {
+            break;
+        }
+    }
+
+    if compressed_chunk_overlaying_crc32 != cfg_header.compressed_chunk_overlying_crc32 {
+        report.chained_crc32_mismatch = Some((
+            cfg_header.compressed_chunk_overlying_crc32,
+            compressed_chunk_overlaying_crc32,
+        ));
+    }
+
+    if decompressed_total != cfg_header.uncompressed_file_size {
+        report.uncompressed_size_mismatch =
+            Some((cfg_header.uncompressed_file_size, decompressed_total));
+    }
+
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codec::CfgEncoder;
+    use crate::format::READ_CHUNK_SIZE;
+    use std::io::{Cursor, Write};
+
+    #[test]
+    fn verify_reports_ok_on_encoder_output() {
+        let xml: Vec<u8> = (0..READ_CHUNK_SIZE * 3 + 1234).map(|i| (i % 256) as u8).collect();
+
+        let mut encoder = CfgEncoder::with_jobs(Vec::new(), "ZXHN F450V2", 4);
+        encoder.write_all(&xml).unwrap();
+        let packed = encoder.finish().unwrap();
+
+        let report = verify_reader(Cursor::new(packed), None, None).unwrap();
+        assert!(report.is_ok());
+    }
+}